@@ -8,10 +8,10 @@ use crate::{
         BENCHMARK_ITERATIONS_PER_SETTING, PROGRESS_UPDATE_INTERVALL,
     },
     result::Result,
-    utils::{clamp, get_random_affix_combination, get_total_combinations, round_even},
+    utils::{clamp, get_total_combinations, round_even},
 };
-use std::{cell::RefCell, collections::HashMap};
-use wasm_bindgen::JsValue;
+use std::{cell::RefCell, collections::HashMap, sync::OnceLock};
+use wasm_bindgen::{JsCast, JsValue};
 use web_sys::{console, DedicatedWorkerGlobalScope};
 
 /// Starts the optimization process. Calculates all possible combinations for the given chunk (subtree) of the affix tree.
@@ -36,14 +36,39 @@ pub fn start(
     // we store our results in a Result object
     let mut result: Result = Result::new(result_num as usize);
 
+    // when the caller supplies a weight vector over Damage/Survivability/EffectiveHealth/
+    // EffectiveHealing we additionally rank by the weighted sum of the raw objective values (they are
+    // not rescaled to a common range), and track the Pareto-optimal frontier across those same
+    // weighted objectives
+    let mut weighted_top: Vec<Character> = vec![];
+    let mut pareto_set: Vec<Character> = vec![];
+
     let counter = RefCell::new(0);
     let mut character = Character::new(rankby);
 
     let max_depth = settings.slots;
 
+    // anytime mode: a budget of 0 means "run to completion", matching today's behavior
+    let time_budget_ms = settings.timeBudgetMs;
+    let start_time_ms = now_ms();
+    let mut out_of_time = false;
+
+    // fuel budget: unlike timeBudgetMs this is a count of attribute-derivation calls (test_character
+    // invocations), not wall-clock time, so the same settings/combinations/chunks always exhaust the
+    // fuel at exactly the same leaf - a deterministic, reproducible stopping point. 0 means "run to
+    // completion", same convention as timeBudgetMs.
+    let fuel_budget = settings.fuelBudget;
+    let mut out_of_fuel = false;
+
+    // one ScalingTable per combination, computed once up front rather than recomputed on every one
+    // of the millions of leaves that combination is tested against
+    let scaling_tables = scaling_tables_for(combinations);
+    let mut cache = AttributeDerivationCache::new(settings.derivationCacheCapacity as usize);
+
     // this callback is called for every affix combination (leaf). this is where we calculate the resulting stats
     // crucuial to optimize every call in this function as it will be called millions of times
-    let mut callback = |subtree: &[Affix]| {
+    // returns false once the time budget is exceeded, telling descend_subtree_dfs to stop descending
+    let mut callback = |subtree: &[Affix]| -> bool {
         // Leaf callback implementation
 
         // iterate over all combinations
@@ -53,62 +78,519 @@ pub fn start(
             character.combination_id = i as u32;
 
             // calculate stats for this combination
-            let valid = test_character(&mut character, settings, combination, subtree);
+            let valid = test_character(
+                &mut character,
+                settings,
+                combination,
+                subtree,
+                &scaling_tables[i],
+                &mut cache,
+            );
 
             if valid {
                 // insert into result_characters if better than worst character
                 result.insert(&character);
+
+                if let Some(weights) = &settings.objectiveWeights {
+                    insert_weighted(&mut weighted_top, &character, weights, result_num as usize);
+                    insert_pareto(&mut pareto_set, &character, weights);
+                }
             }
             *counter.borrow_mut() += 1;
 
+            // checked every call (not gated by PROGRESS_UPDATE_INTERVALL like the time budget below)
+            // so the fuel tank empties at exactly the same derivation call every run
+            if fuel_budget > 0 && *counter.borrow() >= fuel_budget {
+                out_of_fuel = true;
+                post_progress(
+                    settings,
+                    combinations,
+                    &mut result,
+                    workerglobal,
+                    total_combinations,
+                    &weighted_top,
+                    &pareto_set,
+                    true,
+                );
+                return false;
+            }
+
             // post message to js
             if *counter.borrow() % PROGRESS_UPDATE_INTERVALL == 0 {
-                result.on_complete(settings, combinations);
-
-                // get json value of best characters
-                let mut best_combinations: Vec<Combination> = vec![];
-                let mut combination_indices: HashMap<u32, usize> = HashMap::new();
-                let mut best_characters = result.best_characters.clone();
-
-                best_characters.iter_mut().for_each(|character| {
-                    let combination = combinations.get(character.combination_id as usize).unwrap();
-                    let current_id = character.combination_id;
-                    if let Some(comb_index) = combination_indices.get(&current_id) {
-                        character.combination_id = *comb_index as u32;
-                    } else {
-                        let comb_index = best_combinations.len();
-                        combination_indices.insert(current_id, comb_index);
-                        best_combinations.push(combination.clone());
-                        character.combination_id = comb_index as u32;
-                    }
-                });
-                let best_character_json = serde_json::to_string(&best_characters).unwrap();
-                let best_combinations_json = serde_json::to_string(&best_combinations).unwrap();
-
-                workerglobal.and_then(|w| {
-                    w.post_message(&JsValue::from_str(&format!(
-                        "{{ \"type\": \"PROGRESS\", \"total\": {}, \"new\": {}, \"results\": {}, \"combinations\": {} }}",
-                        total_combinations, PROGRESS_UPDATE_INTERVALL,best_character_json, best_combinations_json
-                    )))
-                    .ok()
-                });
+                if time_budget_ms > 0.0 && now_ms() - start_time_ms > time_budget_ms {
+                    out_of_time = true;
+                    post_progress(
+                        settings,
+                        combinations,
+                        &mut result,
+                        workerglobal,
+                        total_combinations,
+                        &weighted_top,
+                        &pareto_set,
+                        true,
+                    );
+                    return false;
+                }
+
+                post_progress(
+                    settings,
+                    combinations,
+                    &mut result,
+                    workerglobal,
+                    total_combinations,
+                    &weighted_top,
+                    &pareto_set,
+                    false,
+                );
             }
         }
+
+        true
     };
 
-    for chunk in chunks {
+    'chunks: for chunk in chunks {
         // start dfs into tree
-        descend_subtree_dfs(
+        if !descend_subtree_dfs(
             &settings.affixesArray,
             chunk,
             max_depth as usize,
             &mut callback,
+        ) {
+            break 'chunks;
+        }
+    }
+
+    // flush a final progress message so the JS layer sees the last weighted top-k/Pareto set even
+    // if the run ended between PROGRESS_UPDATE_INTERVALL boundaries. a time-budget or fuel-budget
+    // expiry already posted its own (partial) final message from inside the callback.
+    if settings.objectiveWeights.is_some() && !out_of_time && !out_of_fuel {
+        post_progress(
+            settings,
+            combinations,
+            &mut result,
+            workerglobal,
+            total_combinations,
+            &weighted_top,
+            &pareto_set,
+            false,
         );
     }
 
     result
 }
 
+/// Debugging helper: binary-searches `settings.fuelBudget` for the smallest fuel budget that still
+/// makes `found` return true against a `start` run's result, by repeatedly re-running `start` with a
+/// probed budget. Meant for localizing when a surprising candidate first gets discovered during a
+/// long search - e.g. `found` checking whether `result.best_characters` contains a specific
+/// combination_id/gear pairing - rather than for production use, since it re-runs the whole search
+/// up to `1 + ceil(log2(max_fuel))` times.
+///
+/// # Arguments
+/// * `chunks`, `settings`, `combinations` - same as `start`; each probe overrides
+///   `settings.fuelBudget` on a clone and otherwise reuses the caller's settings unchanged.
+/// * `max_fuel` - upper bound on the search range, typically `total_combinations` for the run.
+/// * `found` - returns true once a `start` result satisfies whatever the caller is localizing.
+///
+/// Returns `max_fuel` unchanged if even a probe at `max_fuel` doesn't satisfy `found`, since there is
+/// then no minimum budget to localize.
+pub fn bisect_fuel_for_target<F>(
+    chunks: &Vec<Vec<Affix>>,
+    settings: &Settings,
+    combinations: &Vec<Combination>,
+    max_fuel: u64,
+    mut found: F,
+) -> u64
+where
+    F: FnMut(&Result) -> bool,
+{
+    let mut probe_settings = settings.clone();
+
+    probe_settings.fuelBudget = max_fuel;
+    if !found(&start(chunks, &probe_settings, combinations, None)) {
+        return max_fuel;
+    }
+
+    // start at 1, not 0: `fuelBudget == 0` means "unbounded" to `start` (it only checks the fuel
+    // counter when `fuel_budget > 0`), so a `mid` of 0 would run a full, unbudgeted search rather
+    // than probing "zero fuel" - letting `low` reach 0 would make this return 0 as if zero fuel
+    // were enough to localize the target, when it actually means no cap was applied at all.
+    let mut low = 1u64;
+    let mut high = max_fuel;
+
+    while low < high {
+        let mid = low + (high - low) / 2;
+        probe_settings.fuelBudget = mid;
+
+        if found(&start(chunks, &probe_settings, combinations, None)) {
+            high = mid;
+        } else {
+            low = mid + 1;
+        }
+    }
+
+    low
+}
+
+/// Current time in milliseconds, via `performance.now()`. `start` runs inside a
+/// `DedicatedWorkerGlobalScope`, not a `Window` - there is no `window` there, so reading
+/// `web_sys::window()` always returns `None` and silently breaks every caller of this function (the
+/// anytime time budget would never fire, the default RNG seed would always be the same value).
+/// `js_sys::global()` gives the actual global scope regardless of which kind it is, so cast to
+/// `DedicatedWorkerGlobalScope` to reach its `performance()` there instead. Falls back to 0 outside
+/// a worker (e.g. a non-wasm test context) where that cast fails.
+fn now_ms() -> f64 {
+    js_sys::global()
+        .dyn_into::<DedicatedWorkerGlobalScope>()
+        .ok()
+        .and_then(|scope| scope.performance())
+        .map(|p| p.now())
+        .unwrap_or(0.0)
+}
+
+/// Serializes the current best characters/combinations and posts a `PROGRESS` message to the JS layer.
+/// Shared by every search mode (exhaustive DFS, UCB1-guided, ...) so progress reporting stays identical
+/// regardless of how the leaves are being produced. `weighted_top` and `pareto_set` are the weighted-sum
+/// ranking and Pareto-optimal frontier maintained by the caller when `settings.objectiveWeights` is set;
+/// pass empty slices when that feature isn't in use. `partial` flags a message posted because a
+/// `settings.timeBudgetMs` or `settings.fuelBudget` ran out rather than because the search actually
+/// finished.
+fn post_progress(
+    settings: &Settings,
+    combinations: &Vec<Combination>,
+    result: &mut Result,
+    workerglobal: Option<&DedicatedWorkerGlobalScope>,
+    total_combinations: u64,
+    weighted_top: &[Character],
+    pareto_set: &[Character],
+    partial: bool,
+) {
+    result.on_complete(settings, combinations);
+
+    // get json value of best characters
+    let mut best_combinations: Vec<Combination> = vec![];
+    let mut combination_indices: HashMap<u32, usize> = HashMap::new();
+    let mut best_characters = result.best_characters.clone();
+
+    best_characters.iter_mut().for_each(|character| {
+        let combination = combinations.get(character.combination_id as usize).unwrap();
+        let current_id = character.combination_id;
+        if let Some(comb_index) = combination_indices.get(&current_id) {
+            character.combination_id = *comb_index as u32;
+        } else {
+            let comb_index = best_combinations.len();
+            combination_indices.insert(current_id, comb_index);
+            best_combinations.push(combination.clone());
+            character.combination_id = comb_index as u32;
+        }
+    });
+    let best_character_json = serde_json::to_string(&best_characters).unwrap();
+    let best_combinations_json = serde_json::to_string(&best_combinations).unwrap();
+    let weighted_top_json = serde_json::to_string(weighted_top).unwrap();
+    let pareto_set_json = serde_json::to_string(pareto_set).unwrap();
+
+    workerglobal.and_then(|w| {
+        w.post_message(&JsValue::from_str(&format!(
+            "{{ \"type\": \"PROGRESS\", \"total\": {}, \"new\": {}, \"results\": {}, \"combinations\": {}, \"weightedTop\": {}, \"pareto\": {}, \"partial\": {} }}",
+            total_combinations, PROGRESS_UPDATE_INTERVALL, best_character_json, best_combinations_json, weighted_top_json, pareto_set_json, partial
+        )))
+        .ok()
+    });
+}
+
+/// Weight vector over the independent objectives `update_attributes` already computes (Damage,
+/// Survivability, EffectiveHealth, EffectiveHealing), borrowed from the weighted ScoreConfig idea in
+/// the strategy code. Used to rank by a weighted sum instead of a single `rankby` attribute, and to
+/// compare builds when maintaining the Pareto-optimal frontier. The sum is taken over the raw
+/// attribute values; the objectives are not normalized to a common scale.
+#[derive(Clone, Copy, Debug)]
+pub struct ObjectiveWeights {
+    pub damage: f32,
+    pub survivability: f32,
+    pub effectiveHealth: f32,
+    pub effectiveHealing: f32,
+}
+
+impl ObjectiveWeights {
+    const OBJECTIVES: [Attribute; 4] = [
+        Attribute::Damage,
+        Attribute::Survivability,
+        Attribute::EffectiveHealth,
+        Attribute::EffectiveHealing,
+    ];
+
+    fn weights(&self) -> [f32; 4] {
+        [
+            self.damage,
+            self.survivability,
+            self.effectiveHealth,
+            self.effectiveHealing,
+        ]
+    }
+
+    fn score(&self, character: &Character) -> f32 {
+        Self::OBJECTIVES
+            .iter()
+            .zip(self.weights().iter())
+            .map(|(attribute, weight)| weight * character.attributes.get_a(*attribute))
+            .sum()
+    }
+}
+
+/// Returns true if `a` is at least as good as `b` on every weighted objective and strictly better on
+/// at least one, i.e. `b` is Pareto-dominated by `a`.
+fn dominates(a: &Character, b: &Character, weights: &ObjectiveWeights) -> bool {
+    let mut strictly_better = false;
+
+    for (attribute, weight) in ObjectiveWeights::OBJECTIVES.iter().zip(weights.weights().iter()) {
+        let a_val = weight * a.attributes.get_a(*attribute);
+        let b_val = weight * b.attributes.get_a(*attribute);
+
+        if a_val < b_val {
+            return false;
+        }
+        if a_val > b_val {
+            strictly_better = true;
+        }
+    }
+
+    strictly_better
+}
+
+/// Keeps the top `cap` characters ranked by the weighted sum, inserted in sorted order and truncated
+/// once the cap is exceeded. Mirrors the bound `Result` keeps on `rankby`, but over `weights` instead
+/// of a single attribute.
+fn insert_weighted(
+    weighted_top: &mut Vec<Character>,
+    character: &Character,
+    weights: &ObjectiveWeights,
+    cap: usize,
+) {
+    let score = weights.score(character);
+    let position = weighted_top
+        .iter()
+        .position(|existing| weights.score(existing) < score)
+        .unwrap_or(weighted_top.len());
+    weighted_top.insert(position, character.clone());
+    weighted_top.truncate(cap);
+}
+
+/// Maintains a Pareto-optimal frontier over `weights`: inserts `character` unless some existing
+/// member already dominates it, and evicts any existing members that `character` itself dominates.
+fn insert_pareto(pareto_set: &mut Vec<Character>, character: &Character, weights: &ObjectiveWeights) {
+    if pareto_set.iter().any(|existing| dominates(existing, character, weights)) {
+        return;
+    }
+    pareto_set.retain(|existing| !dominates(character, existing, weights));
+    pareto_set.push(character.clone());
+}
+
+/// Below this number of total combinations, `start_mcts` falls back to the exhaustive `start`/
+/// `descend_subtree_dfs` path instead of sampling, since enumerating the whole tree is cheap enough
+/// to just do it exactly and UCB1 bookkeeping wouldn't pay for itself.
+const MCTS_EXHAUSTIVE_THRESHOLD: u64 = 1_000_000;
+
+/// One node of the UCB1 search tree used by `start_mcts`. Each depth corresponds to a slot
+/// (`settings.slots`); a node's `children` are indexed the same way as `settings.affixesArray[depth]`,
+/// so `children[i]` is `None` until the affix option at that index has actually been explored.
+struct McNode {
+    visits: u32,
+    total_reward: f64,
+    children: Vec<Option<McNode>>,
+}
+
+impl McNode {
+    fn new(num_children: usize) -> McNode {
+        McNode {
+            visits: 0,
+            total_reward: 0.0,
+            children: (0..num_children).map(|_| None).collect(),
+        }
+    }
+
+    fn mean_reward(&self) -> f64 {
+        if self.visits == 0 {
+            0.0
+        } else {
+            self.total_reward / self.visits as f64
+        }
+    }
+}
+
+/// Picks the child maximizing UCB1 = mean_reward + C*sqrt(ln(parent_visits) / child_visits), with
+/// C = sqrt(2). Unvisited children score +infinity so every option at a node is tried once before
+/// any of them is revisited.
+fn ucb1_select(node: &McNode) -> usize {
+    const C: f64 = std::f64::consts::SQRT_2;
+
+    let mut best_index = 0;
+    let mut best_score = f64::NEG_INFINITY;
+
+    for (index, child) in node.children.iter().enumerate() {
+        let score = match child {
+            None => f64::INFINITY,
+            Some(child) => {
+                child.mean_reward() + C * ((node.visits as f64).ln() / child.visits as f64).sqrt()
+            }
+        };
+
+        if score > best_score {
+            best_score = score;
+            best_index = index;
+        }
+    }
+
+    best_index
+}
+
+/// UCB1-guided Monte Carlo search, for affix trees too large for `start` to enumerate exhaustively.
+/// Each of the `settings.slots` slots is a tree level; selection descends by `ucb1_select` to focus
+/// on promising prefixes, a rollout fills the remaining slots with `get_random_affix_combination_seeded` and
+/// evaluates them with `test_character`, and the `rankby` attribute of the resulting character is
+/// normalized against the best/worst values seen so far (the normalization window is updated as new
+/// extremes appear) and backpropagated as the reward up the selected path. This mirrors the
+/// explore/exploit tree search used by the minimax/MCTS strategy code, applied to slot selection
+/// instead of move selection.
+///
+/// # Arguments
+/// * `chunks` - Same as `start`: subtrees of the affix tree distributed to this worker.
+/// * `settings` - The settings. Contains important optimizer settings.
+/// * `combinations` - A vector of extras combinations.
+/// * `workerglobal` - The web worker global scope. Used to post messages to the JS code.
+/// * `iterations` - How many rollouts to perform per chunk before returning.
+pub fn start_mcts(
+    chunks: &Vec<Vec<Affix>>,
+    settings: &Settings,
+    combinations: &Vec<Combination>,
+    workerglobal: Option<&DedicatedWorkerGlobalScope>,
+    iterations: u32,
+) -> Result {
+    let total_combinations = get_total_combinations(settings, combinations.len());
+
+    // small searches stay exact: the UCB1 bookkeeping only pays off once the tree is too big to walk
+    if total_combinations < MCTS_EXHAUSTIVE_THRESHOLD {
+        return start(chunks, settings, combinations, workerglobal);
+    }
+
+    let rankby = settings.rankby;
+    let mut result: Result = Result::new(settings.maxResults as usize);
+    let mut character = Character::new(rankby);
+    let counter = RefCell::new(0);
+
+    let max_depth = settings.slots as usize;
+    let mut best_seen = f32::NEG_INFINITY;
+    let mut worst_seen = f32::INFINITY;
+    let mut rng = Mt19937::new(seed_from_settings(settings));
+
+    let scaling_tables = scaling_tables_for(combinations);
+    let mut cache = AttributeDerivationCache::new(settings.derivationCacheCapacity as usize);
+
+    for chunk in chunks {
+        let root_depth = chunk.len();
+        let mut root = McNode::new(settings.affixesArray[root_depth].len());
+
+        for _ in 0..iterations {
+            // selection: descend picking the UCB1-best child until we run off the explored frontier
+            let mut path: Vec<usize> = Vec::with_capacity(max_depth - root_depth);
+            let mut prefix: Vec<Affix> = chunk.clone();
+            let mut node = &mut root;
+
+            while prefix.len() < max_depth {
+                let choice = ucb1_select(node);
+                let depth = prefix.len();
+                prefix.push(settings.affixesArray[depth][choice]);
+                path.push(choice);
+
+                // expansion: the first time selection reaches an unvisited child, create its node
+                // and stop - that's the one node this iteration adds to the tree. Standard MCTS
+                // expands a single node per simulation; the rest of the way to a leaf is left for
+                // the random rollout below rather than walked (and allocated) node-by-node.
+                let is_new_node = node.children[choice].is_none();
+                if is_new_node {
+                    let next_depth = depth + 1;
+                    let num_children = if next_depth < max_depth {
+                        settings.affixesArray[next_depth].len()
+                    } else {
+                        0
+                    };
+                    node.children[choice] = Some(McNode::new(num_children));
+                }
+                node = node.children[choice].as_mut().unwrap();
+
+                if is_new_node {
+                    break;
+                }
+            }
+
+            // rollout: fill whatever slots selection didn't reach with random affixes
+            if prefix.len() < max_depth {
+                let random_tail = get_random_affix_combination_seeded(
+                    &settings.affixesArray[prefix.len()..max_depth],
+                    max_depth - prefix.len(),
+                    &mut rng,
+                );
+                prefix.extend(random_tail);
+            }
+
+            let mut reward = 0.0f64;
+            for i in 0..combinations.len() {
+                let combination = &combinations[i];
+                character.clear();
+                character.combination_id = i as u32;
+
+                let valid = test_character(
+                    &mut character,
+                    settings,
+                    combination,
+                    &prefix,
+                    &scaling_tables[i],
+                    &mut cache,
+                );
+                if valid {
+                    result.insert(&character);
+
+                    let rank_value = character.attributes.get_a(rankby);
+                    best_seen = best_seen.max(rank_value);
+                    worst_seen = worst_seen.min(rank_value);
+
+                    let normalized = if best_seen > worst_seen {
+                        ((rank_value - worst_seen) / (best_seen - worst_seen)) as f64
+                    } else {
+                        0.5
+                    };
+                    reward = reward.max(normalized);
+                }
+
+                *counter.borrow_mut() += 1;
+                if *counter.borrow() % PROGRESS_UPDATE_INTERVALL == 0 {
+                    post_progress(
+                        settings,
+                        combinations,
+                        &mut result,
+                        workerglobal,
+                        total_combinations,
+                        &[],
+                        &[],
+                        false,
+                    );
+                }
+            }
+
+            // backpropagation: every node on the selected path gets the same rollout reward
+            root.visits += 1;
+            root.total_reward += reward;
+            let mut node = &mut root;
+            for &choice in &path {
+                node = node.children[choice].as_mut().unwrap();
+                node.visits += 1;
+                node.total_reward += reward;
+            }
+        }
+    }
+
+    result
+}
+
 /// Runs a couple benchmarking runs first to get a good heuristics which combinations to test first.
 /// Each combination is tested BENCHMARK_ITERATIONS_PER_SETTING times with random affix combinations.
 /// We figure out how often each setting in the resulting character appears in the top BENCMARK_ITERATIONS_PER_SETTING characters.
@@ -125,17 +607,31 @@ pub fn start_with_heuristics(settings: &Settings, combinations: &Vec<Combination
 
     // benchmark a few results first to get a good heuristics which combinations to test first
     let mut character = Character::new(settings.rankby);
+    let mut rng = Mt19937::new(seed_from_settings(settings));
+
+    let scaling_tables = scaling_tables_for(combinations);
+    let mut cache = AttributeDerivationCache::new(settings.derivationCacheCapacity as usize);
 
     for (index, combination) in combinations.iter().enumerate() {
         for _ in 0..BENCHMARK_ITERATIONS_PER_SETTING {
             character.clear();
             character.combination_id = index as u32;
 
-            let gear =
-                get_random_affix_combination(&settings.affixesArray, settings.slots as usize);
+            let gear = get_random_affix_combination_seeded(
+                &settings.affixesArray,
+                settings.slots as usize,
+                &mut rng,
+            );
 
             // calculate stats for this combination
-            let valid = test_character(&mut character, settings, combination, &gear);
+            let valid = test_character(
+                &mut character,
+                settings,
+                combination,
+                &gear,
+                &scaling_tables[index],
+                &mut cache,
+            );
             if valid {
                 // insert into result_characters if better than worst character
                 result.insert(&character);
@@ -171,32 +667,125 @@ pub fn start_with_heuristics(settings: &Settings, combinations: &Vec<Combination
 /// # Arguments
 /// * `affix_array` - An array of vectors of affixes. Each entry in the array corresponds to the affixes selectable for a specific slot. The array is of length 14, because there are 14 slots. However, if the last slot is not used due to two-handed weapons, the last entry in the array is Affix::None
 /// * `subtree` - The current subtree of the affix tree. This is a vector of affixes. The length of the vector is the current layer of the tree. The first entry in the vector is the root of the tree.
-/// * `leaf_callback` - A function that is called when a leaf of the tree is reached. The function is passed the current subtree.
+/// * `leaf_callback` - A function that is called when a leaf of the tree is reached. The function is passed the current subtree and returns whether the search should keep going; returning `false` (e.g. because a time budget expired) unwinds the whole descent without visiting further leaves.
 pub fn descend_subtree_dfs<F>(
     affix_array: &[Vec<Affix>],
     subtree: &[Affix],
     max_depth: usize,
     leaf_callback: &mut F,
-) where
-    F: FnMut(&[Affix]),
+) -> bool
+where
+    F: FnMut(&[Affix]) -> bool,
 {
     let current_layer = subtree.len();
 
     if current_layer == max_depth {
         // if we reached leafs of the tree, call the function
-        leaf_callback(subtree);
-    } else {
-        let permutation_options = &affix_array[current_layer];
+        return leaf_callback(subtree);
+    }
+
+    let permutation_options = &affix_array[current_layer];
+
+    let mut new_subtree: Vec<Affix> = Vec::with_capacity(subtree.len() + 1);
+    new_subtree.clear();
+    new_subtree.extend_from_slice(subtree);
+
+    for &option in permutation_options {
+        new_subtree.push(option);
+        let keep_going = descend_subtree_dfs(affix_array, &new_subtree, max_depth, leaf_callback);
+        new_subtree.pop();
 
-        let mut new_subtree: Vec<Affix> = Vec::with_capacity(subtree.len() + 1);
-        new_subtree.clear();
-        new_subtree.extend_from_slice(subtree);
+        if !keep_going {
+            return false;
+        }
+    }
+
+    true
+}
 
-        for &option in permutation_options {
-            new_subtree.push(option);
-            descend_subtree_dfs(affix_array, &new_subtree, max_depth, leaf_callback);
-            new_subtree.pop();
+/// Self-contained MT19937 Mersenne Twister, used so that, given the same `settings.seed`, settings
+/// and combinations, the benchmark/rollout phases pick exactly the same "random" affix combinations
+/// every run. Standard 624-word state and tempering, the same approach other simulation codebases
+/// reached for when they dropped libc `rand()` in favor of deterministic, higher-quality output.
+pub struct Mt19937 {
+    state: [u32; 624],
+    index: usize,
+}
+
+impl Mt19937 {
+    pub fn new(seed: u32) -> Mt19937 {
+        let mut state = [0u32; 624];
+        state[0] = seed;
+        for i in 1..624 {
+            state[i] = 1812433253u32
+                .wrapping_mul(state[i - 1] ^ (state[i - 1] >> 30))
+                .wrapping_add(i as u32);
         }
+        Mt19937 { state, index: 624 }
+    }
+
+    fn generate(&mut self) {
+        for i in 0..624 {
+            let y = (self.state[i] & 0x8000_0000) + (self.state[(i + 1) % 624] & 0x7fff_ffff);
+            self.state[i] = self.state[(i + 397) % 624] ^ (y >> 1);
+            if y % 2 != 0 {
+                self.state[i] ^= 0x9908_b0df;
+            }
+        }
+        self.index = 0;
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        if self.index >= 624 {
+            self.generate();
+        }
+
+        let mut y = self.state[self.index];
+        y ^= y >> 11;
+        y ^= (y << 7) & 0x9d2c_5680;
+        y ^= (y << 15) & 0xefc6_0000;
+        y ^= y >> 18;
+
+        self.index += 1;
+        y
+    }
+
+    /// Returns a float in [0, 1), matching the range the ad-hoc RNG it replaces produced.
+    pub fn next_f64(&mut self) -> f64 {
+        self.next_u32() as f64 / (u32::MAX as f64 + 1.0)
+    }
+
+    /// Returns an index in [0, len), for picking a random affix out of a slot's option list.
+    pub fn gen_range(&mut self, len: usize) -> usize {
+        (self.next_f64() * len as f64) as usize
+    }
+}
+
+/// Seeded replacement for `get_random_affix_combination`: fills the first `num_slots` entries of
+/// `affix_array` with a random option drawn from `rng`, so the same seed/settings/combinations
+/// always produce the same "random" picks.
+pub fn get_random_affix_combination_seeded(
+    affix_array: &[Vec<Affix>],
+    num_slots: usize,
+    rng: &mut Mt19937,
+) -> Vec<Affix> {
+    affix_array
+        .iter()
+        .take(num_slots)
+        .map(|options| options[rng.gen_range(options.len())])
+        .collect()
+}
+
+/// Picks the RNG seed to use: `settings.seed` when the caller supplied one (nonzero), otherwise
+/// derived from `now_ms()`'s `performance.now()`, preserving today's non-reproducible default. This
+/// depends on `now_ms()` actually reading the worker's clock rather than `window()` (which is absent
+/// in a `DedicatedWorkerGlobalScope` and would otherwise make every default-seed run read 0 and so
+/// collapse to the same "random" sequence).
+pub fn seed_from_settings(settings: &Settings) -> u32 {
+    if settings.seed != 0 {
+        settings.seed
+    } else {
+        now_ms() as u32
     }
 }
 
@@ -205,6 +794,8 @@ pub fn test_character(
     settings: &Settings,
     combination: &Combination,
     subtree: &[Affix],
+    scaling: &ScalingTable,
+    cache: &mut AttributeDerivationCache,
 ) -> bool {
     // add base attributes from settings to character
     combination.baseAttributes.iter().for_each(|(key, value)| {
@@ -235,7 +826,7 @@ pub fn test_character(
     }
 
     // calculate stats for the character
-    update_attributes(character, settings, combination, false)
+    update_attributes(character, settings, combination, false, scaling, cache)
 }
 
 pub fn update_attributes(
@@ -243,19 +834,31 @@ pub fn update_attributes(
     settings: &Settings,
     combination: &Combination,
     no_rounding: bool,
+    scaling: &ScalingTable,
+    cache: &mut AttributeDerivationCache,
 ) -> bool {
-    calc_stats(character, settings, combination, no_rounding);
+    calc_stats(character, settings, combination, no_rounding, scaling);
 
     if character.is_invalid(settings) {
         return false;
     }
 
-    let power_damage_score = calc_power(character, settings, combination);
+    // the rest of this function - power/condi/survivability/healing - is the attribute-combination
+    // function AttributeDerivationCache memoizes: a pure function of the post-calc_stats attribute
+    // map, so a cache hit here can skip straight to the cached result
+    let key = AttributeKey::from_character(character);
+    if let Some(derived) = cache.get(&key) {
+        character.attributes = derived.clone();
+        return true;
+    }
+
+    let power_damage_score = calc_power(character, settings, combination, scaling);
     let condi_damage_score = calc_condi(
         character,
         settings,
         combination,
         &combination.relevantConditions,
+        scaling,
     );
 
     character.attributes.set_a(
@@ -263,9 +866,11 @@ pub fn update_attributes(
         power_damage_score + condi_damage_score + character.attributes.get_a(Attribute::FlatDPS),
     );
 
-    calc_survivability(character, combination);
+    calc_survivability(character, combination, scaling);
     calc_healing(character);
 
+    cache.insert(key, character.attributes.clone());
+
     true
 }
 
@@ -274,6 +879,7 @@ fn calc_stats(
     settings: &Settings,
     combination: &Combination,
     no_rounding: bool,
+    scaling: &ScalingTable,
 ) {
     // move base attributes to attributes as default
     // not sure which method is faster, but I think the for loop is faster:
@@ -370,18 +976,24 @@ fn calc_stats(
         }
     }
 
+    // combination.consumables picks the food/utility this combination is evaluated with, either
+    // pinned across every combination or varied as an extra search dimension alongside runes/sigils.
+    // applied before the point-to-percentage conversions below so e.g. a Ferocity utility reaches
+    // CriticalDamage
+    apply_consumables(attributes, &combination.consumables);
+
     // recalculate attributes
     attributes.add_a(
         Attribute::CriticalChance,
-        (attributes.get_a(Attribute::Precision) - 1000.0) / 21.0 / 100.0,
+        (attributes.get_a(Attribute::Precision) - 1000.0) * scaling.critical_chance_per_precision,
     );
     attributes.add_a(
         Attribute::CriticalDamage,
-        attributes.get_a(Attribute::Ferocity) / 15.0 / 100.0,
+        attributes.get_a(Attribute::Ferocity) * scaling.critical_damage_per_ferocity,
     );
     attributes.add_a(
         Attribute::BoonDuration,
-        attributes.get_a(Attribute::Concentration) / 15.0 / 100.0,
+        attributes.get_a(Attribute::Concentration) * scaling.boon_duration_per_concentration,
     );
     attributes.set_a(
         Attribute::Health,
@@ -395,15 +1007,15 @@ fn calc_stats(
     if settings.profession.eq("Mesmer") {
         attributes.add_a(
             Attribute::CloneCriticalChance,
-            (attributes.get_a(Attribute::Precision) - 1000.0) / 21.0 / 100.0,
+            (attributes.get_a(Attribute::Precision) - 1000.0) * scaling.critical_chance_per_precision,
         );
         attributes.add_a(
             Attribute::PhantasmCriticalChance,
-            (attributes.get_a(Attribute::Precision) - 1000.0) / 21.0 / 100.0,
+            (attributes.get_a(Attribute::Precision) - 1000.0) * scaling.critical_chance_per_precision,
         );
         attributes.add_a(
             Attribute::PhantasmCriticalDamage,
-            attributes.get_a(Attribute::Ferocity) / 15.0 / 100.0,
+            attributes.get_a(Attribute::Ferocity) * scaling.critical_damage_per_ferocity,
         );
     } else if attributes.get_a(Attribute::Power2Coefficient) > 0.0 {
         attributes.set_a(
@@ -414,13 +1026,13 @@ fn calc_stats(
             Attribute::AltCriticalChance,
             attributes.get_a(Attribute::AltCriticalChance)
                 + attributes.get_a(Attribute::CriticalChance)
-                + attributes.get_a(Attribute::AltPrecision) / 21.0 / 100.0,
+                + attributes.get_a(Attribute::AltPrecision) * scaling.critical_chance_per_precision,
         );
         attributes.set_a(
             Attribute::AltCriticalDamage,
             attributes.get_a(Attribute::AltCriticalDamage)
                 + attributes.get_a(Attribute::CriticalDamage)
-                + attributes.get_a(Attribute::AltFerocity) / 15.0 / 100.0,
+                + attributes.get_a(Attribute::AltFerocity) * scaling.critical_damage_per_ferocity,
         );
     }
 }
@@ -429,6 +1041,7 @@ pub fn calc_power(
     character: &mut Character,
     settings: &Settings,
     combination: &Combination,
+    scaling: &ScalingTable,
 ) -> f32 {
     let attributes = &mut character.attributes;
     let mods = &combination.modifiers;
@@ -449,11 +1062,17 @@ pub fn calc_power(
             * mods.get_dmg_multiplier(Attribute::OutgoingStrikeDamage),
     );
 
-    // 2597: standard enemy armor value, also used for ingame damage tooltips
-    let mut power_damage = (attributes.get_a(Attribute::PowerCoefficient) / 2597.0)
+    let target_profile = &combination.targetProfile;
+
+    // combination.targetProfile.armor replaces the old hardcoded 2597 standard-enemy armor value
+    // (also used for ingame damage tooltips), so builds can be ranked against a different boss/pack.
+    // scaling.inverse_armor is 1.0 / target_profile.armor (floored at MIN_ARMOR), precomputed once per combination instead
+    // of dividing by it on every leaf
+    let mut power_damage = ((attributes.get_a(Attribute::PowerCoefficient) * scaling.inverse_armor)
         * attributes.get_a(Attribute::EffectivePower)
-        + (attributes.get_a(Attribute::NonCritPowerCoefficient) / 2597.0)
-            * attributes.get_a(Attribute::NonCritEffectivePower);
+        + (attributes.get_a(Attribute::NonCritPowerCoefficient) * scaling.inverse_armor)
+            * attributes.get_a(Attribute::NonCritEffectivePower))
+        * cleave_multiplier(target_profile, target_profile.powerCleaves);
     // this is nowhere read again?
     attributes.set_a(Attribute::PowerDPS, power_damage);
 
@@ -475,8 +1094,10 @@ pub fn calc_power(
                     * mods.get_dmg_multiplier(Attribute::OutgoingPhantasmDamage),
             );
 
-            let phantasm_power_damage = (attributes.get_a(Attribute::Power2Coefficient) / 2597.0)
-                * attributes.get_a(Attribute::PhantasmEffectivePower);
+            let phantasm_power_damage = (attributes.get_a(Attribute::Power2Coefficient)
+                * scaling.inverse_armor)
+                * attributes.get_a(Attribute::PhantasmEffectivePower)
+                * cleave_multiplier(target_profile, target_profile.power2Cleaves);
             attributes.set_a(Attribute::Power2DPS, phantasm_power_damage);
             power_damage += phantasm_power_damage;
         } else {
@@ -492,8 +1113,10 @@ pub fn calc_power(
                     * mods.get_dmg_multiplier(Attribute::OutgoingAltDamage),
             );
 
-            let alt_power_damage = (attributes.get_a(Attribute::Power2Coefficient) / 2597.0)
-                * attributes.get_a(Attribute::AltEffectivePower);
+            let alt_power_damage = (attributes.get_a(Attribute::Power2Coefficient)
+                * scaling.inverse_armor)
+                * attributes.get_a(Attribute::AltEffectivePower)
+                * cleave_multiplier(target_profile, target_profile.power2Cleaves);
             attributes.set_a(Attribute::Power2DPS, alt_power_damage);
             power_damage += alt_power_damage;
         }
@@ -502,13 +1125,158 @@ pub fn calc_power(
     }
 
     let siphon_damage = attributes.get_a(Attribute::SiphonBaseCoefficient)
-        * mods.get_dmg_multiplier(Attribute::OutgoingSiphonDamage);
+        * mods.get_dmg_multiplier(Attribute::OutgoingSiphonDamage)
+        * cleave_multiplier(target_profile, target_profile.siphonCleaves);
 
     attributes.set_a(Attribute::SiphonDPS, siphon_damage);
 
     power_damage + siphon_damage
 }
 
+/// Target profile for a combination: configurable defender armor and target count, instead of
+/// the previously-hardcoded single "standard enemy". Lets the optimizer rank a build against a
+/// high-armor single boss or against a low-armor, multi-target (cleave) pack.
+///
+/// # Fields
+/// * `armor` - Defender armor divided into strike/phantasm/alt power coefficients. 2597 is the
+///   in-game "standard enemy" value also used for ingame damage tooltips.
+/// * `targetCount` - Number of targets simultaneously hit by cleave-eligible damage.
+/// * `powerCleaves` / `power2Cleaves` / `siphonCleaves` / `condiCleaves` - Per-coefficient cleave
+///   eligibility. `false` means that damage component only ever hits one target regardless of
+///   `targetCount` (e.g. a single-target siphon).
+#[derive(Clone, Copy, Debug)]
+pub struct TargetProfile {
+    pub armor: f32,
+    pub targetCount: f32,
+    pub powerCleaves: bool,
+    pub power2Cleaves: bool,
+    pub siphonCleaves: bool,
+    pub condiCleaves: bool,
+}
+
+/// Returns `target_profile.targetCount` (at least `1.0`) for cleave-eligible damage, or `1.0` for single-target-only
+/// damage, so multiplying a damage component by this always yields the correct per-player score.
+fn cleave_multiplier(target_profile: &TargetProfile, cleaves: bool) -> f32 {
+    if cleaves {
+        target_profile.targetCount.max(1.0)
+    } else {
+        1.0
+    }
+}
+
+/// Precomputed per-combination conversion factors for the formulas `calc_stats`/`calc_power`/
+/// `calc_survivability` apply to every leaf: crit-chance-per-precision, crit-damage-per-ferocity,
+/// boon-duration-per-concentration, condition-duration-per-expertise, and the reciprocals of
+/// `target_profile.armor` and the survivability normalization constant. None of these change within
+/// a combination, so `scaling_tables_for` computes one `ScalingTable` per combination up front
+/// instead of every formula re-deriving its factor (and, for the armor/survivability reciprocals,
+/// re-dividing) on every one of the millions of leaves that combination is tested against.
+#[derive(Clone, Copy, Debug)]
+pub struct ScalingTable {
+    pub critical_chance_per_precision: f32,
+    pub critical_damage_per_ferocity: f32,
+    pub boon_duration_per_concentration: f32,
+    pub condition_duration_per_expertise: f32,
+    pub inverse_armor: f32,
+    pub inverse_survivability_denom: f32,
+}
+
+/// Floor for `targetProfile.armor` before it's inverted into `ScalingTable::inverse_armor`. Unlike
+/// the old hardcoded `2597.0` standard-enemy armor (which could never be zero), `armor` is now
+/// caller-supplied via `TargetProfile`, so an unvalidated `0.0` would divide out to `inf` power
+/// damage instead of erroring. `1.0` keeps `inverse_armor` finite while still being far below any
+/// realistic defender armor, so it only ever bites a caller that passed a bogus value.
+const MIN_ARMOR: f32 = 1.0;
+
+impl ScalingTable {
+    fn from_combination(combination: &Combination) -> ScalingTable {
+        ScalingTable {
+            critical_chance_per_precision: 1.0 / 21.0 / 100.0,
+            critical_damage_per_ferocity: 1.0 / 15.0 / 100.0,
+            boon_duration_per_concentration: 1.0 / 15.0 / 100.0,
+            condition_duration_per_expertise: 1.0 / 15.0 / 100.0,
+            inverse_armor: 1.0 / combination.targetProfile.armor.max(MIN_ARMOR),
+            inverse_survivability_denom: 1.0 / 1967.0,
+        }
+    }
+}
+
+/// Builds one `ScalingTable` per entry in `combinations`, aligned by index, so `start`/`start_mcts`/
+/// `start_with_heuristics` can index straight into it (`&scaling_tables[i]`) instead of recomputing
+/// the same conversion factors inside their per-leaf hot loops.
+fn scaling_tables_for(combinations: &Vec<Combination>) -> Vec<ScalingTable> {
+    combinations
+        .iter()
+        .map(ScalingTable::from_combination)
+        .collect()
+}
+
+/// Canonicalized key into `AttributeDerivationCache`: every attribute value rounded the same way
+/// `calc_stats` rounds point-key attributes, then folded into a single FNV-1a hash so the key can be
+/// hashed without relying on float equality, plus the combination id so combinations with different
+/// modifiers/targetProfile/relevantConditions never share an entry. Gear combinations that land on
+/// the same final post-`calc_stats` attribute totals - including permutation-equivalent ones, where
+/// the same flat bonuses were merely picked up in a different order - canonicalize to the same key.
+///
+/// Folded into a `u64` rather than collected into a `Vec<u32>`: `from_character` runs once per leaf
+/// inside `update_attributes`'s hot loop, so building the key must stay cheap relative to the
+/// derivation it's memoizing, and a `Copy` tuple of two integers avoids a per-call heap allocation
+/// (and the hasher then walking that `Vec`) entirely. A hash collision between two different
+/// attribute totals of the same combination serves the cached derived attributes of the other
+/// totals; the combination id keeps collisions from crossing between combinations.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct AttributeKey(u32, u64);
+
+impl AttributeKey {
+    fn from_character(character: &Character) -> AttributeKey {
+        // FNV-1a fold over the rounded attribute bits.
+        let mut hash: u64 = 0xcbf29ce484222325;
+        for i in 0..character.attributes.len() {
+            hash ^= round_even(character.attributes[i]).to_bits() as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+        AttributeKey(character.combination_id, hash)
+    }
+}
+
+/// Memoizes the attribute-combination function - the power/condi/survivability/healing derivation
+/// `update_attributes` runs after `calc_stats` - keyed on `AttributeKey`, so the enormous number of
+/// near-identical or permutation-equivalent attribute totals a full search produces are served from
+/// cache rather than re-derived. Bounded by `capacity`: once the cache would grow past it, it is
+/// cleared rather than allowed to grow unbounded, trading a burst of cache misses for a hard memory
+/// ceiling. `capacity == 0` disables caching entirely (`get` always misses, `insert` is a no-op), so
+/// memory-constrained callers can opt out via `settings.derivationCacheCapacity`.
+pub struct AttributeDerivationCache {
+    entries: HashMap<AttributeKey, AttributesArray>,
+    capacity: usize,
+}
+
+impl AttributeDerivationCache {
+    pub fn new(capacity: usize) -> AttributeDerivationCache {
+        AttributeDerivationCache {
+            entries: HashMap::new(),
+            capacity,
+        }
+    }
+
+    fn get(&self, key: &AttributeKey) -> Option<&AttributesArray> {
+        if self.capacity == 0 {
+            return None;
+        }
+        self.entries.get(key)
+    }
+
+    fn insert(&mut self, key: AttributeKey, derived: AttributesArray) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() >= self.capacity {
+            self.entries.clear();
+        }
+        self.entries.insert(key, derived);
+    }
+}
+
 /// Calculates a damage tick for a given condition
 ///
 /// # Arguments
@@ -532,13 +1300,14 @@ pub fn calc_condi(
     settings: &Settings,
     combination: &Combination,
     relevant_conditions: &[Condition],
+    scaling: &ScalingTable,
 ) -> f32 {
     let attributes = &mut character.attributes;
     let mods = &combination.modifiers;
 
     attributes.add_a(
         Attribute::ConditionDuration,
-        attributes.get_a(Attribute::Expertise) / 15.0 / 100.0,
+        attributes.get_a(Attribute::Expertise) * scaling.condition_duration_per_expertise,
     );
 
     let mut condi_damage_score = 0.0;
@@ -592,10 +1361,10 @@ pub fn calc_condi(
         condi_damage_score += dps;
     }
 
-    condi_damage_score
+    condi_damage_score * cleave_multiplier(&combination.targetProfile, combination.targetProfile.condiCleaves)
 }
 
-fn calc_survivability(character: &mut Character, combination: &Combination) {
+fn calc_survivability(character: &mut Character, combination: &Combination, scaling: &ScalingTable) {
     let attributes = &mut character.attributes;
     let mods = &combination.modifiers;
 
@@ -610,7 +1379,7 @@ fn calc_survivability(character: &mut Character, combination: &Combination) {
 
     attributes.set_a(
         Attribute::Survivability,
-        attributes.get_a(Attribute::EffectiveHealth) / 1967.0,
+        attributes.get_a(Attribute::EffectiveHealth) * scaling.inverse_survivability_denom,
     );
 }
 
@@ -625,10 +1394,407 @@ fn calc_healing(character: &mut Character) {
             * (1.0 + attributes.get_a(Attribute::OutgoingHealing)),
     );
 
-    // TODO add bountiful maintenance oil
-
     attributes.set_a(
         Attribute::Healing,
         attributes.get_a(Attribute::EffectiveHealing),
     );
 }
+
+/// One ordered step applied to the attribute map by a consumable (food or utility). Mirrors the
+/// convert/buff/convertAfterBuffs phases `calc_stats` already runs for combination modifiers, but
+/// scoped to a single consumable and resolved once, after those phases, in `apply_consumables`.
+#[derive(Clone, Debug)]
+pub enum ConsumableModifier {
+    /// Adds a flat amount to `attribute`.
+    Flat(Attribute, f32),
+    /// Adds `attribute`'s own current value times `percent`, e.g. +10% outgoing healing.
+    Percent(Attribute, f32),
+    /// Adds `percent` of `source`'s current value to `target`, e.g. "gain healing power equal to
+    /// 10% of concentration". The attribute-to-attribute case flat/percent alone can't express.
+    Convert {
+        target: Attribute,
+        source: Attribute,
+        percent: f32,
+    },
+}
+
+/// A single food or utility item: a name for display and an ordered list of `ConsumableModifier`s,
+/// applied in sequence so a later step (e.g. a `Convert`) can read an earlier step's result.
+#[derive(Clone, Debug)]
+pub struct Consumable {
+    pub name: &'static str,
+    pub modifiers: Vec<ConsumableModifier>,
+}
+
+/// Which food and/or utility a combination is being evaluated with, by index into `food_table`/
+/// `utility_table`. `None` means no consumable of that category, the same convention `Combination`
+/// already uses elsewhere for an unused slot. A `Combination` carries one of these; pinning a
+/// choice means every combination the JS layer generates shares the same loadout, while treating
+/// food/utility as an extra search dimension means it varies the loadout across combinations the
+/// same way it already varies runes/sigils - no change to `start`/`start_mcts` is needed either way,
+/// since they already loop over whatever `combinations` they're handed.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ConsumableLoadout {
+    pub food: Option<usize>,
+    pub utility: Option<usize>,
+}
+
+/// The foods consumables can be picked from, indexed by `ConsumableLoadout::food`. Built once into a
+/// `OnceLock` rather than freshly allocated per call: `apply_consumables` runs from `calc_stats`,
+/// i.e. once per leaf, so reallocating the table (and every modifier `Vec` nested inside it) on
+/// every one of the millions of calls that make would be a hot-loop regression.
+pub fn food_table() -> &'static Vec<Consumable> {
+    static TABLE: OnceLock<Vec<Consumable>> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        vec![
+            Consumable {
+                name: "Bowl of Sweet and Spicy Butternut Squash Soup",
+                modifiers: vec![ConsumableModifier::Flat(Attribute::ConditionDamage, 100.0)],
+            },
+            Consumable {
+                name: "Bowl of Fancy Potato and Leek Soup",
+                modifiers: vec![ConsumableModifier::Flat(Attribute::HealingPower, 100.0)],
+            },
+        ]
+    })
+}
+
+/// The utilities consumables can be picked from, see `food_table`. Bountiful Maintenance Oil is the
+/// motivating example for the `Convert` modifier: it grants both a flat outgoing-healing bonus and
+/// healing power scaled off concentration, so a flat/percent-only format couldn't express it.
+pub fn utility_table() -> &'static Vec<Consumable> {
+    static TABLE: OnceLock<Vec<Consumable>> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        vec![
+            Consumable {
+                name: "Bountiful Maintenance Oil",
+                modifiers: vec![
+                    ConsumableModifier::Flat(Attribute::OutgoingHealing, 0.1),
+                    ConsumableModifier::Convert {
+                        target: Attribute::HealingPower,
+                        source: Attribute::Concentration,
+                        percent: 0.1,
+                    },
+                ],
+            },
+            Consumable {
+                name: "Superior Sharpening Stone",
+                modifiers: vec![ConsumableModifier::Flat(Attribute::Ferocity, 100.0)],
+            },
+        ]
+    })
+}
+
+/// Applies every modifier in `consumable.modifiers`, in order, to `attributes`. `Convert` reads its
+/// source from `attributes` rather than `base_attributes`, so it can scale off an attribute a prior
+/// modifier already changed - in this consumable or an earlier one in the loadout - matching how
+/// `calc_stats`'s `convertAfterBuffs` phase resolves against the post-buff value.
+fn apply_consumable_modifiers(attributes: &mut AttributesArray, consumable: &Consumable) {
+    for modifier in &consumable.modifiers {
+        match modifier {
+            ConsumableModifier::Flat(attribute, amount) => {
+                attributes.add_a(*attribute, *amount);
+            }
+            ConsumableModifier::Percent(attribute, percent) => {
+                let current = attributes.get_a(*attribute);
+                attributes.add_a(*attribute, current * percent);
+            }
+            ConsumableModifier::Convert {
+                target,
+                source,
+                percent,
+            } => {
+                attributes.add_a(*target, attributes.get_a(*source) * percent);
+            }
+        }
+    }
+}
+
+/// Applies `loadout`'s chosen food and utility (if any), food first then utility, looking each up by
+/// index in `food_table`/`utility_table`. Called from `calc_stats` after the combination's own
+/// convert/buff/convertAfterBuffs phases, so consumables see the fully-buffed attribute map, and
+/// before `calc_power`/`calc_condi`/`calc_survivability`/`calc_healing` derive the effective stats
+/// those consumables are meant to influence. Bails out before touching either table when `loadout`
+/// is empty, since this runs once per leaf and most searches pin or omit consumables entirely.
+fn apply_consumables(attributes: &mut AttributesArray, loadout: &ConsumableLoadout) {
+    if loadout.food.is_none() && loadout.utility.is_none() {
+        return;
+    }
+
+    if let Some(index) = loadout.food {
+        apply_consumable_modifiers(attributes, &food_table()[index]);
+    }
+    if let Some(index) = loadout.utility {
+        apply_consumable_modifiers(attributes, &utility_table()[index]);
+    }
+}
+
+/// Forward-mode dual number: a value paired with its partial derivatives with respect to a fixed
+/// set of decision variables (e.g. points allocated to each stat, or fractional infusion counts).
+/// Every arithmetic op below propagates derivatives by the standard forward-mode rules:
+/// `(a+b)' = a'+b'`, `(a*b)' = a'b + ab'`, `(a/b)' = (a'b - ab')/b^2`. Build a `constant` for values
+/// that don't depend on the decision variables (zero gradient) and a `variable` for ones that do
+/// (gradient 1 in their own slot).
+#[derive(Clone, Debug)]
+pub struct Dual {
+    pub value: f64,
+    pub grad: Vec<f64>,
+}
+
+impl Dual {
+    // `constant` lives only on `Scalar` (below) - an inherent copy here would drift from it, and
+    // every caller of this module already needs `Scalar` in scope for `f64`'s impl anyway.
+    pub fn variable(value: f64, index: usize, num_vars: usize) -> Dual {
+        let mut grad = vec![0.0; num_vars];
+        grad[index] = 1.0;
+        Dual { value, grad }
+    }
+}
+
+impl std::ops::Add for Dual {
+    type Output = Dual;
+    fn add(self, rhs: Dual) -> Dual {
+        assert_eq!(
+            self.grad.len(),
+            rhs.grad.len(),
+            "Dual::add between mismatched gradient widths - one operand was built with the wrong num_vars"
+        );
+        Dual {
+            value: self.value + rhs.value,
+            grad: self
+                .grad
+                .iter()
+                .zip(rhs.grad.iter())
+                .map(|(a, b)| a + b)
+                .collect(),
+        }
+    }
+}
+
+impl std::ops::Sub for Dual {
+    type Output = Dual;
+    fn sub(self, rhs: Dual) -> Dual {
+        assert_eq!(
+            self.grad.len(),
+            rhs.grad.len(),
+            "Dual::sub between mismatched gradient widths - one operand was built with the wrong num_vars"
+        );
+        Dual {
+            value: self.value - rhs.value,
+            grad: self
+                .grad
+                .iter()
+                .zip(rhs.grad.iter())
+                .map(|(a, b)| a - b)
+                .collect(),
+        }
+    }
+}
+
+impl std::ops::Mul for Dual {
+    type Output = Dual;
+    fn mul(self, rhs: Dual) -> Dual {
+        assert_eq!(
+            self.grad.len(),
+            rhs.grad.len(),
+            "Dual::mul between mismatched gradient widths - one operand was built with the wrong num_vars"
+        );
+        Dual {
+            value: self.value * rhs.value,
+            grad: self
+                .grad
+                .iter()
+                .zip(rhs.grad.iter())
+                .map(|(da, db)| da * rhs.value + self.value * db)
+                .collect(),
+        }
+    }
+}
+
+impl std::ops::Div for Dual {
+    type Output = Dual;
+    fn div(self, rhs: Dual) -> Dual {
+        assert_eq!(
+            self.grad.len(),
+            rhs.grad.len(),
+            "Dual::div between mismatched gradient widths - one operand was built with the wrong num_vars"
+        );
+        let denom = rhs.value * rhs.value;
+        Dual {
+            value: self.value / rhs.value,
+            grad: self
+                .grad
+                .iter()
+                .zip(rhs.grad.iter())
+                .map(|(da, db)| (da * rhs.value - self.value * db) / denom)
+                .collect(),
+        }
+    }
+}
+
+/// Minimal numeric trait implemented by both `f64` and `Dual`, so the attribute-derivation formulas
+/// (the `EffectiveHealing` -> `OutgoingHealing` -> `Healing` chain built via `get_a`/`set_a`, the
+/// `calc_power`/`calc_condi` damage formulas, ...) can be written once and run either as plain
+/// scalars or carrying derivatives, rather than duplicating each formula per mode.
+///
+/// `constant` takes `num_vars` (rather than inferring it) so a `Dual` constant always comes out
+/// with the same gradient width as the `Dual::variable`s it's mixed with - a `Dual` built with the
+/// wrong `num_vars` would otherwise either panic (see the `assert_eq!`s in `Dual`'s arithmetic impls)
+/// or, worse, silently combine with the wrong slot. `f64`'s impl ignores it since a plain scalar has
+/// no gradient to size.
+pub trait Scalar:
+    Clone
+    + std::ops::Add<Output = Self>
+    + std::ops::Sub<Output = Self>
+    + std::ops::Mul<Output = Self>
+    + std::ops::Div<Output = Self>
+{
+    fn constant(value: f64, num_vars: usize) -> Self;
+}
+
+impl Scalar for f64 {
+    fn constant(value: f64, _num_vars: usize) -> f64 {
+        value
+    }
+}
+
+impl Scalar for Dual {
+    fn constant(value: f64, num_vars: usize) -> Dual {
+        Dual { value, grad: vec![0.0; num_vars] }
+    }
+}
+
+/// Forward-mode generic mirror of `calc_power`'s core damage formula: `effective_power` (power
+/// scaled up by crit chance/damage and outgoing-strike-damage modifiers) combined with the
+/// power/non-crit-power coefficients, `inverse_armor`, and cleave multiplier into a single damage
+/// number - the same shape as the real `crit_dmg`/`crit_chance`/`EffectivePower`/`NonCritEffectivePower`/
+/// `PowerDPS` computation in `calc_power`, just taking its inputs as explicit `Scalar` arguments
+/// instead of reading/writing `character.attributes`. Pass `Dual::variable` for whichever input is a
+/// decision variable (points allocated to a stat, a fractional infusion count, ...) and
+/// `Scalar::constant(value, num_vars)` (same `num_vars`) for the rest; the result's gradient gives
+/// the objective's sensitivity to each one in a single pass, instead of finite-differencing
+/// `calc_power` once per stat.
+///
+/// This covers `calc_power`'s single-power-coefficient path (no Mesmer phantasm/alt-power branch,
+/// no siphon damage) - the part of the formula that doesn't depend on `settings.profession` or
+/// branch on `Power2Coefficient`. Reaching the full "whole attribute-combination module is generic
+/// over `Dual`" deliverable would mean making `AttributesArray` itself generic over `Scalar`, since
+/// `calc_stats`/`calc_power`/`calc_condi`/`calc_healing` all read and write it through `get_a`/`set_a`
+/// in place - and `AttributesArray`/`Character` are owned by the `character` module, outside this
+/// crate pass, so that change is out of scope here. This function is the faithful foundation for
+/// that follow-up (and already gives a real gradient for the common path), not a drop-in replacement
+/// for the full pipeline yet.
+pub fn calc_power_generic<S: Scalar>(
+    num_vars: usize,
+    power: S,
+    crit_chance: S,
+    crit_dmg: S,
+    outgoing_strike_dmg_mult: S,
+    power_coefficient: S,
+    non_crit_power_coefficient: S,
+    inverse_armor: S,
+    cleave_multiplier: S,
+) -> S {
+    let one = S::constant(1.0, num_vars);
+    let effective_power = power.clone()
+        * (one.clone() + crit_chance * (crit_dmg - one))
+        * outgoing_strike_dmg_mult.clone();
+    let non_crit_effective_power = power * outgoing_strike_dmg_mult;
+
+    ((power_coefficient * inverse_armor.clone()) * effective_power
+        + (non_crit_power_coefficient * inverse_armor) * non_crit_effective_power)
+        * cleave_multiplier
+}
+
+/// Configuration for `continuous_solve`'s projected-gradient-with-momentum search.
+pub struct ContinuousSolverConfig {
+    pub stepCount: u32,
+    pub learningRate: f64,
+    pub momentum: f64,
+    pub seed: u64,
+}
+
+/// Clamps every coordinate to be non-negative, then rescales so the vector sums to exactly `budget`
+/// (a no-op if every coordinate was already zero). This is the simplex `allocation` must stay on:
+/// a continuous relaxation of "spend exactly `budget` stat points across these dimensions".
+pub fn project_to_simplex(allocation: &mut [f64], budget: f64) {
+    for value in allocation.iter_mut() {
+        if *value < 0.0 {
+            *value = 0.0;
+        }
+    }
+    let sum: f64 = allocation.iter().sum();
+    if sum > 0.0 {
+        let scale = budget / sum;
+        for value in allocation.iter_mut() {
+            *value *= scale;
+        }
+    }
+}
+
+/// One projected-gradient-ascent-with-momentum step (ascent since the objective, DPS/effective
+/// healing, is being maximized): accumulates `gradient` into `velocity` with `config.momentum`
+/// carried over from the previous step, applies it to `allocation`, then reprojects onto the budget
+/// simplex via `project_to_simplex` so the next gradient is evaluated at a still-feasible point.
+pub fn sgd_step(
+    allocation: &mut [f64],
+    velocity: &mut [f64],
+    gradient: &[f64],
+    config: &ContinuousSolverConfig,
+    budget: f64,
+) {
+    for i in 0..allocation.len() {
+        velocity[i] = config.momentum * velocity[i] + config.learningRate * gradient[i];
+        allocation[i] += velocity[i];
+    }
+    project_to_simplex(allocation, budget);
+}
+
+/// Continuous optimizer backend, for build spaces too large to brute-force with `start`/`start_mcts`.
+/// Models the stat/infusion distribution as a continuous `num_dimensions`-vector that sums to
+/// `budget`, seeds it (via `config.seed`, for reproducible runs) with an even split perturbed by a
+/// small amount of Mt19937 noise, then runs `config.stepCount` projected-gradient steps. At each step
+/// `objective_gradient` is expected to evaluate the differentiable objective's gradient at the
+/// current allocation - typically by wrapping each coordinate in a `Dual::variable` and running it
+/// through `calc_power_generic`. Snapping the returned relaxed allocation to the nearest feasible
+/// integer gear layout and locally searching that neighborhood (e.g. restricting
+/// `descend_subtree_dfs` to nearby affixes) is left to the caller, since the snapping is specific to
+/// how `settings.affixesArray` enumerates options; the relaxed result is also a strong warm start for
+/// that exact search.
+///
+/// No caller in this crate wires `objective_gradient` up to the real build objective yet: doing so
+/// needs the rest of `calc_stats`/`calc_condi`/`calc_healing` generified over `Scalar` the way
+/// `calc_power_generic` generifies `calc_power`'s core path, which `AttributesArray` living in the
+/// separate `character` module (see `calc_power_generic`'s doc comment) puts out of scope for this
+/// pass. Until a caller does that wiring, this function is a correctly-implemented projected-gradient
+/// core with no production caller, not a finished feature - callers today must supply their own
+/// `objective_gradient` (e.g. a finite-difference approximation) to use it at all.
+pub fn continuous_solve<F>(
+    num_dimensions: usize,
+    budget: f64,
+    config: &ContinuousSolverConfig,
+    mut objective_gradient: F,
+) -> Vec<f64>
+where
+    F: FnMut(&[f64]) -> Vec<f64>,
+{
+    // `config.seed` is a full u64 (see `ContinuousSolverConfig`), but `Mt19937` only accepts a u32
+    // seed - fold the high and low halves together with xor rather than silently truncating to the
+    // low 32 bits, so the upper bits still influence the sequence instead of being discarded.
+    let folded_seed = (config.seed as u32) ^ ((config.seed >> 32) as u32);
+    let mut rng = Mt19937::new(folded_seed);
+    let even_share = budget / num_dimensions as f64;
+    let mut allocation: Vec<f64> = (0..num_dimensions)
+        .map(|_| even_share * (0.9 + 0.2 * rng.next_f64()))
+        .collect();
+    project_to_simplex(&mut allocation, budget);
+
+    let mut velocity = vec![0.0; num_dimensions];
+
+    for _ in 0..config.stepCount {
+        let gradient = objective_gradient(&allocation);
+        sgd_step(&mut allocation, &mut velocity, &gradient, config, budget);
+    }
+
+    allocation
+}